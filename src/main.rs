@@ -5,6 +5,257 @@ use std::fs::File;
 use std::path::Path;
 use hound::{WavWriter, WavSpec, SampleFormat, WavReader};
 use rfd::FileDialog;
+use symphonia::core::audio::{AudioBufferRef, Signal};
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+const TARGET_SAMPLE_RATE: u32 = 16000;
+
+/// 자막 한 줄(세그먼트) 정보. `t0`/`t1`은 Whisper의 10ms 단위 타임스탬프다.
+struct Segment {
+    index: usize,
+    t0: i64,
+    t1: i64,
+    text: String,
+}
+
+/// 출력 자막 포맷.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Srt,
+    Vtt,
+    Txt,
+    Json,
+    Csv,
+    Tsv,
+}
+
+impl OutputFormat {
+    fn from_arg(arg: &str) -> Option<Self> {
+        match arg.to_lowercase().as_str() {
+            "srt" => Some(Self::Srt),
+            "vtt" => Some(Self::Vtt),
+            "txt" => Some(Self::Txt),
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            "tsv" => Some(Self::Tsv),
+            _ => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Srt => "srt",
+            Self::Vtt => "vtt",
+            Self::Txt => "txt",
+            Self::Json => "json",
+            Self::Csv => "csv",
+            Self::Tsv => "tsv",
+        }
+    }
+}
+
+/// CLI 인자 목록에서 `flag` 바로 다음 값을 찾아 `T`로 파싱한다. 없거나 파싱 실패 시 `None`.
+fn arg_value<T: std::str::FromStr>(flag: &str) -> Option<T> {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter()
+        .position(|a| a == flag)
+        .and_then(|i| args.get(i + 1))
+        .and_then(|v| v.parse::<T>().ok())
+}
+
+/// CLI 인자에 `flag`가 값 없이 존재하는지 확인한다 (예: `--translate`).
+fn has_flag(flag: &str) -> bool {
+    std::env::args().any(|a| a == flag)
+}
+
+/// CLI 인자에서 원하는 출력 포맷을 읽는다. `--format <fmt>` 형태이며, 생략 시 SRT.
+fn parse_output_format() -> OutputFormat {
+    arg_value::<String>("--format")
+        .and_then(|fmt| OutputFormat::from_arg(&fmt))
+        .unwrap_or(OutputFormat::Srt)
+}
+
+/// 줄당 최대 글자 수. `--max-len <n>` 형태이며, 생략 시 제한 없음(0).
+fn parse_max_len() -> i32 {
+    arg_value("--max-len").unwrap_or(0)
+}
+
+/// 단어 경계에서만 줄을 나눌지 여부. `--split-on-word` 플래그.
+fn parse_split_on_word() -> bool {
+    has_flag("--split-on-word")
+}
+
+/// 언어 옵션. `--language <code>`가 없으면 `None`을 반환해 모델이 자동 감지하게 둔다.
+/// `--language auto`를 명시해도 동일하게 동작한다.
+fn parse_language() -> Option<String> {
+    arg_value::<String>("--language").filter(|l| !l.eq_ignore_ascii_case("auto"))
+}
+
+/// `--translate` 플래그가 있으면 결과를 영어로 번역한다.
+fn parse_translate() -> bool {
+    has_flag("--translate")
+}
+
+/// `--diarize` 플래그가 있으면 2채널 소스에서 좌/우 채널 에너지를 비교해 화자를 표시한다.
+fn parse_diarize() -> bool {
+    has_flag("--diarize")
+}
+
+/// `--offset-ms <n>`: 오디오의 이 지점부터 변환을 시작한다(기본 0).
+fn parse_offset_ms() -> i64 {
+    arg_value("--offset-ms").unwrap_or(0)
+}
+
+/// `--duration-ms <n>`: 오프셋 이후 이 길이만큼만 변환한다(기본 0 = 끝까지).
+fn parse_duration_ms() -> i64 {
+    arg_value("--duration-ms").unwrap_or(0)
+}
+
+/// `--segment-index-offset <n>`: 기존 SRT에 이어붙일 때 자막 번호를 이 값만큼 밀어준다.
+fn parse_segment_index_offset() -> usize {
+    arg_value("--segment-index-offset").unwrap_or(0)
+}
+
+/// 16kHz 샘플 구간의 RMS(평균 에너지)를 구한다.
+fn channel_energy(samples: &[f32], start_sample: usize, end_sample: usize) -> f32 {
+    let start = start_sample.min(samples.len());
+    let end = end_sample.min(samples.len()).max(start);
+    if end == start {
+        return 0.0;
+    }
+
+    let sum_sq: f32 = samples[start..end].iter().map(|s| s * s).sum();
+    (sum_sq / (end - start) as f32).sqrt()
+}
+
+/// 세그먼트 구간(Whisper 10ms 단위)의 좌/우 채널 에너지를 비교해 화자 번호(0/1)를 고른다.
+fn label_speaker(left: &[f32], right: &[f32], t0: i64, t1: i64) -> usize {
+    let start_sample = (t0 * 10) as usize * (TARGET_SAMPLE_RATE as usize / 1000);
+    let end_sample = (t1 * 10) as usize * (TARGET_SAMPLE_RATE as usize / 1000);
+
+    let left_energy = channel_energy(left, start_sample, end_sample);
+    let right_energy = channel_energy(right, start_sample, end_sample);
+
+    if right_energy > left_energy {
+        1
+    } else {
+        0
+    }
+}
+
+/// 디코딩 전략 설정. 빔 서치가 탐욕적(greedy) 디코딩보다 어려운 오디오에서 품질이 더 좋다.
+enum DecodingConfig {
+    BeamSearch { beam_size: i32 },
+    Greedy { best_of: i32 },
+}
+
+/// `--beam-search` 플래그나 `--beam-size` 값이 주어지면 빔 서치(`--beam-size`, 기본 5)를,
+/// 아니면 탐욕적 디코딩(`--best-of`, 기본 5)을 사용한다.
+fn parse_decoding_config() -> DecodingConfig {
+    let beam_size = arg_value::<i32>("--beam-size");
+
+    if has_flag("--beam-search") || beam_size.is_some() {
+        return DecodingConfig::BeamSearch {
+            beam_size: beam_size.unwrap_or(5),
+        };
+    }
+
+    DecodingConfig::Greedy {
+        best_of: arg_value("--best-of").unwrap_or(5),
+    }
+}
+
+fn sampling_strategy(config: &DecodingConfig) -> SamplingStrategy {
+    match *config {
+        DecodingConfig::BeamSearch { beam_size } => SamplingStrategy::BeamSearch {
+            beam_size,
+            patience: -1.0,
+        },
+        DecodingConfig::Greedy { best_of } => SamplingStrategy::Greedy { best_of },
+    }
+}
+
+/// 단어 타임스탬프 확률 임계값. `--word-thold <f32>`, 생략 시 0.01.
+fn parse_word_thold() -> f32 {
+    arg_value("--word-thold").unwrap_or(0.01)
+}
+
+/// Whisper 토큰이 `[_BEG_]`, `[_TT_123]` 같은 특수 토큰인지 확인한다.
+fn is_special_token(text: &str) -> bool {
+    let t = text.trim();
+    t.starts_with("[_") && t.ends_with(']')
+}
+
+/// `(텍스트, t0, t1)` 토큰 목록을 `max_len` 글자 단위로 줄바꿈한다. `split_on_word`가
+/// true면 단어 중간에서 끊지 않는다. Whisper API와 분리된 순수 함수라 모델 없이 테스트할 수 있다.
+fn wrap_tokens_into_lines(
+    tokens: &[(String, i64, i64)],
+    max_len: i32,
+    split_on_word: bool,
+) -> Vec<(i64, i64, String)> {
+    // 일본어 등 CJK 언어의 BPE 토큰은 영어와 달리 단어 시작에 공백을 붙이지 않는 경우가
+    // 대부분이라, 토큰 중 공백으로 시작하는 것이 하나도 없으면 공백 휴리스틱을 포기하고
+    // 글자 경계마다 줄바꿈을 허용한다 (그렇지 않으면 --split-on-word가 --max-len을 무력화한다).
+    let has_space_initial_tokens = tokens.iter().any(|(text, _, _)| text.starts_with(' '));
+
+    let mut lines = Vec::new();
+    let mut line_text = String::new();
+    let mut line_t0: Option<i64> = None;
+    let mut line_t1: i64 = 0;
+
+    for (token_text, t0, t1) in tokens {
+        let starts_new_word = line_text.is_empty()
+            || !has_space_initial_tokens
+            || token_text.starts_with(' ');
+        let would_overflow = max_len > 0
+            && (line_text.chars().count() + token_text.chars().count()) as i32 > max_len;
+
+        if would_overflow && !line_text.is_empty() && (!split_on_word || starts_new_word) {
+            lines.push((line_t0.unwrap_or(0), line_t1, line_text.trim().to_string()));
+            line_text.clear();
+            line_t0 = None;
+        }
+
+        if line_t0.is_none() {
+            line_t0 = Some(*t0);
+        }
+        line_t1 = *t1;
+        line_text.push_str(token_text);
+    }
+
+    if !line_text.trim().is_empty() {
+        lines.push((line_t0.unwrap_or(0), line_t1, line_text.trim().to_string()));
+    }
+
+    lines
+}
+
+/// 세그먼트의 토큰 타임스탬프를 이용해 `max_len` 글자 단위로 줄을 나눈다.
+/// `split_on_word`가 true면 단어 중간에서 끊지 않는다.
+fn split_segment_into_lines(
+    state: &whisper_rs::WhisperState,
+    segment_idx: i32,
+    max_len: i32,
+    split_on_word: bool,
+) -> anyhow::Result<Vec<(i64, i64, String)>> {
+    let num_tokens = state.full_n_tokens(segment_idx)?;
+    let mut tokens = Vec::with_capacity(num_tokens as usize);
+
+    for j in 0..num_tokens {
+        let token_text = state.full_get_token_text(segment_idx, j)?;
+        if is_special_token(&token_text) {
+            continue;
+        }
+        let token_data = state.full_get_token_data(segment_idx, j)?;
+        tokens.push((token_text, token_data.t0, token_data.t1));
+    }
+
+    Ok(wrap_tokens_into_lines(&tokens, max_len, split_on_word))
+}
 
 /// SRT 시간 포맷 변환 함수 (Whisper 10ms 단위를 ms로 변환)
 fn format_srt_time(whisper_time: i64) -> String {
@@ -12,7 +263,7 @@ fn format_srt_time(whisper_time: i64) -> String {
     let seconds = milliseconds / 1000;
     let ms = milliseconds % 1000;
     let minutes = seconds / 60;
-    let hours = minutes / 60; 
+    let hours = minutes / 60;
 
     format!(
         "{:02}:{:02}:{:02},{:03}",
@@ -23,34 +274,232 @@ fn format_srt_time(whisper_time: i64) -> String {
     )
 }
 
-fn main() -> anyhow::Result<()> {
-    // 0. 사용자로부터 파일 선택 받기
-    println!("📂 처리할 영상 파일을 선택해주세요...");
-    let file_path = FileDialog::new()
-        .add_filter("Video Files", &["mp4", "mkv", "avi", "mov"])
-        .add_filter("Audio Files", &["wav", "mp3", "m4a"])
-        .set_directory(".") // 현재 폴더에서 시작
-        .pick_file();
+/// WebVTT 시간 포맷 (`HH:MM:SS.mmm`).
+fn format_vtt_time(whisper_time: i64) -> String {
+    let milliseconds = whisper_time * 10;
+    let seconds = milliseconds / 1000;
+    let ms = milliseconds % 1000;
+    let minutes = seconds / 60;
+    let hours = minutes / 60;
 
-    // 사용자가 취소를 눌렀을 경우 처리
-    let input_file = match file_path {
-        Some(path) => path,
-        None => {
-            println!("❌ 파일 선택이 취소되었습니다. 프로그램을 종료합니다.");
-            return Ok(());
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        hours,
+        minutes % 60,
+        seconds % 60,
+        ms
+    )
+}
+
+/// JSON 문자열 내 특수문자를 이스케이프한다.
+fn json_escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t")
+}
+
+fn render_srt(segments: &[Segment]) -> String {
+    let mut out = String::new();
+    for seg in segments {
+        out.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            seg.index,
+            format_srt_time(seg.t0),
+            format_srt_time(seg.t1),
+            seg.text
+        ));
+    }
+    out
+}
+
+fn render_vtt(segments: &[Segment]) -> String {
+    let mut out = String::from("WEBVTT\n\n");
+    for seg in segments {
+        out.push_str(&format!(
+            "{} --> {}\n{}\n\n",
+            format_vtt_time(seg.t0),
+            format_vtt_time(seg.t1),
+            seg.text
+        ));
+    }
+    out
+}
+
+fn render_txt(segments: &[Segment]) -> String {
+    let mut out = String::new();
+    for seg in segments {
+        out.push_str(seg.text.as_str());
+        out.push('\n');
+    }
+    out
+}
+
+fn render_json(segments: &[Segment]) -> String {
+    let mut out = String::from("[\n");
+    for (i, seg) in segments.iter().enumerate() {
+        out.push_str(&format!(
+            "  {{\"start\": {}, \"end\": {}, \"text\": \"{}\"}}",
+            seg.t0 * 10,
+            seg.t1 * 10,
+            json_escape(&seg.text)
+        ));
+        out.push_str(if i + 1 < segments.len() { ",\n" } else { "\n" });
+    }
+    out.push_str("]\n");
+    out
+}
+
+fn render_delimited(segments: &[Segment], delimiter: char) -> String {
+    let mut out = format!("index{delimiter}start_ms{delimiter}end_ms{delimiter}text\n");
+    for seg in segments {
+        out.push_str(&format!(
+            "{}{delimiter}{}{delimiter}{}{delimiter}{}\n",
+            seg.index,
+            seg.t0 * 10,
+            seg.t1 * 10,
+            seg.text
+                .replace(delimiter, " ")
+                .replace(['\n', '\r'], " ")
+        ));
+    }
+    out
+}
+
+fn render_segments(segments: &[Segment], format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Srt => render_srt(segments),
+        OutputFormat::Vtt => render_vtt(segments),
+        OutputFormat::Txt => render_txt(segments),
+        OutputFormat::Json => render_json(segments),
+        OutputFormat::Csv => render_delimited(segments, ','),
+        OutputFormat::Tsv => render_delimited(segments, '\t'),
+    }
+}
+
+/// 선형 보간으로 `samples`(모노)를 `from_rate`에서 `TARGET_SAMPLE_RATE`로 리샘플링한다.
+fn resample_to_target(samples: &[f32], from_rate: u32) -> Vec<f32> {
+    if from_rate == TARGET_SAMPLE_RATE || samples.is_empty() {
+        return samples.to_vec();
+    }
+
+    let ratio = TARGET_SAMPLE_RATE as f64 / from_rate as f64;
+    let out_len = (samples.len() as f64 * ratio).round() as usize;
+    let mut out = Vec::with_capacity(out_len);
+
+    for i in 0..out_len {
+        let src_pos = i as f64 / ratio;
+        let idx = src_pos.floor() as usize;
+        let frac = (src_pos - idx as f64) as f32;
+        let a = samples[idx.min(samples.len() - 1)];
+        let b = samples[(idx + 1).min(samples.len() - 1)];
+        out.push(a + (b - a) * frac);
+    }
+
+    out
+}
+
+/// `symphonia`로 컨테이너를 디코딩해 원본 샘플레이트의 채널별 `f32` PCM을 반환한다.
+/// (source_rate, 채널별 샘플 목록) 형태이며, 모노/스테레오 경로 양쪽에서 재사용한다.
+fn decode_audio_channels(path: &Path) -> anyhow::Result<(u32, Vec<Vec<f32>>)> {
+    let file = File::open(path)?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+
+    let probed = symphonia::default::get_probe().format(
+        &hint,
+        mss,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+
+    let mut format = probed.format;
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != symphonia::core::codecs::CODEC_TYPE_NULL)
+        .ok_or_else(|| anyhow::anyhow!("오디오 트랙을 찾을 수 없습니다."))?
+        .clone();
+
+    let mut decoder =
+        symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+    let track_id = track.id;
+    let source_rate = track
+        .codec_params
+        .sample_rate
+        .ok_or_else(|| anyhow::anyhow!("샘플레이트를 확인할 수 없습니다."))?;
+
+    let mut channels: Vec<Vec<f32>> = Vec::new();
+
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(symphonia::core::errors::Error::IoError(_)) => break,
+            Err(e) => return Err(e.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
         }
-    };
 
-    let input_path_str = input_file.to_str().unwrap();
-    println!("✅ 선택된 파일: {}", input_path_str);
+        let decoded = decoder.decode(&packet)?;
+        let buf = match decoded {
+            AudioBufferRef::F32(buf) => buf.into_owned(),
+            other => {
+                let mut buf = other.make_equivalent::<f32>();
+                other.convert(&mut buf);
+                buf
+            }
+        };
 
-    // --- 설정 변수 ---
-    let output_wav = "temp_audio.wav";                    // 중간 오디오 파일
-    let model_path = "./ggml-kotoba-whisper-v2.0-q5_0.bin";                 // 모델 파일
-    let srt_output = format!("{}.srt", input_file.file_stem().unwrap().to_str().unwrap());
+        let num_channels = buf.spec().channels.count();
+        if channels.is_empty() {
+            channels.resize(num_channels, Vec::new());
+        }
+        for (ch, channel_samples) in channels.iter_mut().enumerate().take(num_channels) {
+            channel_samples.extend(buf.chan(ch));
+        }
+    }
 
-    // 1. 오디오 추출 단계
-    println!("🚀 [1/4] 오디오 추출 시작 (FFmpeg)...");
+    Ok((source_rate, channels))
+}
+
+/// 채널별 샘플을 평균해 모노 16kHz `f32` PCM을 만든다.
+/// mp4/mkv/mp3/m4a/wav 등 symphonia가 지원하는 포맷을 FFmpeg 없이 처리한다.
+fn decode_audio_in_process(path: &Path) -> anyhow::Result<Vec<f32>> {
+    let (source_rate, channels) = decode_audio_channels(path)?;
+    let num_frames = channels.iter().map(|c| c.len()).max().unwrap_or(0);
+    let mut mono = vec![0.0f32; num_frames];
+
+    for ch in &channels {
+        for (i, sample) in ch.iter().enumerate() {
+            mono[i] += sample / channels.len() as f32;
+        }
+    }
+
+    Ok(resample_to_target(&mono, source_rate))
+}
+
+/// 2채널(스테레오) 소스를 좌/우 채널로 분리해 각각 16kHz로 리샘플링한다.
+/// 화자 분리를 위해 채널을 섞지 않고 보존해야 하므로 별도 경로로 제공한다.
+fn decode_audio_stereo_channels(path: &Path) -> anyhow::Result<Option<(Vec<f32>, Vec<f32>)>> {
+    let (source_rate, channels) = decode_audio_channels(path)?;
+    if channels.len() != 2 {
+        return Ok(None);
+    }
+
+    let left = resample_to_target(&channels[0], source_rate);
+    let right = resample_to_target(&channels[1], source_rate);
+    Ok(Some((left, right)))
+}
+
+/// FFmpeg 서브프로세스를 이용한 기존 추출 경로. symphonia가 처리하지 못하는
+/// 특이한 컨테이너를 위한 폴백으로 유지한다.
+fn decode_audio_via_ffmpeg(input_path_str: &str, output_wav: &str) -> anyhow::Result<Vec<f32>> {
     let spec = WavSpec {
         channels: 1,
         sample_rate: 16000,
@@ -83,61 +532,346 @@ fn main() -> anyhow::Result<()> {
     }
     child.wait()?;
     writer.finalize()?;
-    println!("✅ 오디오 추출 완료.");
+
+    let mut wav_reader = WavReader::open(output_wav)?;
+    let audio_data: Vec<f32> = wav_reader
+        .samples::<i16>()
+        .map(|s| s.unwrap() as f32 / 32768.0)
+        .collect();
+
+    std::fs::remove_file(output_wav).ok();
+    Ok(audio_data)
+}
+
+fn main() -> anyhow::Result<()> {
+    // 0. 사용자로부터 파일 선택 받기
+    println!("📂 처리할 영상 파일을 선택해주세요...");
+    let file_path = FileDialog::new()
+        .add_filter("Video Files", &["mp4", "mkv", "avi", "mov"])
+        .add_filter("Audio Files", &["wav", "mp3", "m4a"])
+        .set_directory(".") // 현재 폴더에서 시작
+        .pick_file();
+
+    // 사용자가 취소를 눌렀을 경우 처리
+    let input_file = match file_path {
+        Some(path) => path,
+        None => {
+            println!("❌ 파일 선택이 취소되었습니다. 프로그램을 종료합니다.");
+            return Ok(());
+        }
+    };
+
+    let input_path_str = input_file.to_str().unwrap();
+    println!("✅ 선택된 파일: {}", input_path_str);
+
+    // --- 설정 변수 ---
+    let output_wav = "temp_audio.wav";                    // 중간 오디오 파일
+    let model_path = "./ggml-kotoba-whisper-v2.0-q5_0.bin";                 // 모델 파일
+    let output_format = parse_output_format();
+    let srt_output = format!(
+        "{}.{}",
+        input_file.file_stem().unwrap().to_str().unwrap(),
+        output_format.extension()
+    );
+
+    // 1. 오디오 추출 단계 (symphonia 인프로세스 디코딩, 실패 시 FFmpeg 폴백)
+    println!("🚀 [1/3] 오디오 디코딩 중...");
+    let audio_data = match decode_audio_in_process(&input_file) {
+        Ok(samples) => samples,
+        Err(e) => {
+            println!("⚠️ 인프로세스 디코딩 실패 ({e}), FFmpeg로 재시도합니다...");
+            decode_audio_via_ffmpeg(input_path_str, output_wav)?
+        }
+    };
+    println!("✅ 오디오 디코딩 완료 ({} 샘플).", audio_data.len());
+
+    let diarize = parse_diarize();
+    let speaker_channels = if diarize {
+        match decode_audio_stereo_channels(&input_file) {
+            Ok(Some(channels)) => Some(channels),
+            Ok(None) => {
+                println!("⚠️ 2채널 소스가 아니라 화자 분리를 건너뜁니다.");
+                None
+            }
+            Err(e) => {
+                println!("⚠️ 화자 분리용 채널 분리 실패 ({e}), 건너뜁니다.");
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // (옵션) 구간 자르기: offset_ms~offset_ms+duration_ms 구간만 추론 대상으로 삼는다.
+    let offset_ms = parse_offset_ms();
+    let duration_ms = parse_duration_ms();
+    let segment_index_offset = parse_segment_index_offset();
+    let offset_centiseconds = offset_ms / 10;
+
+    let samples_per_ms = (TARGET_SAMPLE_RATE / 1000) as usize;
+    let start_sample = (offset_ms.max(0) as usize * samples_per_ms).min(audio_data.len());
+    let end_sample = if duration_ms > 0 {
+        (start_sample + duration_ms as usize * samples_per_ms).min(audio_data.len())
+    } else {
+        audio_data.len()
+    };
+    let audio_data = audio_data[start_sample..end_sample].to_vec();
 
     // 2. Whisper 모델 초기화
     if !Path::new(model_path).exists() {
         return Err(anyhow::anyhow!("모델 파일이 없습니다! {}을 확인하세요.", model_path));
     }
-    println!("🚀 [2/4] Whisper 모델 로드 중...");
+    println!("🚀 [2/3] Whisper 모델 로드 중...");
     let ctx = WhisperContext::new_with_params(model_path, WhisperContextParameters::default())?;
 
-    // 3. 오디오 데이터를 f32 Vec으로 로드
-    println!("🎵 [3/4] 오디오 데이터 변환 중...");
-    let mut wav_reader = WavReader::open(output_wav)?;
-    let audio_data: Vec<f32> = wav_reader
-        .samples::<i16>()
-        .map(|s| s.unwrap() as f32 / 32768.0)
-        .collect();
+    // 3. 음성 인식 및 자막 생성
+    let max_len = parse_max_len();
+    let split_on_word = parse_split_on_word();
+    let word_thold = parse_word_thold();
+    let decoding_config = parse_decoding_config();
+    let language = parse_language();
+    let translate = parse_translate();
+
+    let language_label = language.as_deref().unwrap_or("auto-detect");
+    println!(
+        "🤖 [3/3] 음성 인식 및 자막 생성 시작... (언어: {}{})",
+        language_label,
+        if translate { ", 영어로 번역" } else { "" }
+    );
 
-    // 4. 음성 인식 및 자막 생성
-    println!("🤖 [4/4] 일본어 음성 인식 및 자막 생성 시작...");
-    let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
-    params.set_language(Some("ja"));
+    let mut params = FullParams::new(sampling_strategy(&decoding_config));
+    params.set_language(language.as_deref());
+    params.set_translate(translate);
     params.set_print_special(false);
     params.set_print_progress(true);
     params.set_print_timestamps(true);
+    params.set_max_len(max_len);
+    params.set_split_on_word(split_on_word);
+    params.set_token_timestamps(max_len > 0);
+    params.set_thold_pt(word_thold);
+    params.set_entropy_thold(2.4);
+    params.set_logprob_thold(-1.0);
 
     let mut state = ctx.create_state()?;
     state.full(params, &audio_data).expect("추론 실패");
 
+    let detected_lang_id = state.full_lang_id_from_state()?;
+    println!(
+        "🌐 감지된 언어: {}",
+        whisper_rs::get_lang_str(detected_lang_id).unwrap_or("unknown")
+    );
+
     let num_segments = state.full_n_segments()?;
-    let mut srt_content = String::new();
+    let mut segments = Vec::new();
 
     for i in 0..num_segments {
-        let text = state.full_get_segment_text(i)?;
-        let t0 = state.full_get_segment_t0(i)?;
-        let t1 = state.full_get_segment_t1(i)?;
+        if max_len > 0 {
+            let lines = split_segment_into_lines(&state, i, max_len, split_on_word)?;
+            if lines.is_empty() {
+                // 세그먼트의 모든 토큰이 특수 토큰이라 줄바꿈 결과가 비어 있으면,
+                // 세그먼트를 통째로 누락시키지 않고 원본 텍스트/타임스탬프로 대체한다.
+                let text = state.full_get_segment_text(i)?.trim().to_string();
+                if !text.is_empty() {
+                    let t0 = state.full_get_segment_t0(i)?;
+                    let t1 = state.full_get_segment_t1(i)?;
+                    segments.push(Segment {
+                        index: segments.len() + 1 + segment_index_offset,
+                        t0: t0 + offset_centiseconds,
+                        t1: t1 + offset_centiseconds,
+                        text,
+                    });
+                }
+            } else {
+                for (t0, t1, text) in lines {
+                    segments.push(Segment {
+                        index: segments.len() + 1 + segment_index_offset,
+                        t0: t0 + offset_centiseconds,
+                        t1: t1 + offset_centiseconds,
+                        text,
+                    });
+                }
+            }
+        } else {
+            let text = state.full_get_segment_text(i)?;
+            let t0 = state.full_get_segment_t0(i)?;
+            let t1 = state.full_get_segment_t1(i)?;
 
-        let srt_segment = format!(
-            "{}\n{} --> {}\n{}\n\n",
-            i + 1,
-            format_srt_time(t0),
-            format_srt_time(t1),
-            text.trim()
-        );
-        srt_content.push_str(&srt_segment);
+            segments.push(Segment {
+                index: segments.len() + 1 + segment_index_offset,
+                t0: t0 + offset_centiseconds,
+                t1: t1 + offset_centiseconds,
+                text: text.trim().to_string(),
+            });
+        }
+    }
+
+    if let Some((left, right)) = &speaker_channels {
+        for seg in &mut segments {
+            let speaker = label_speaker(left, right, seg.t0, seg.t1);
+            seg.text = format!("(speaker {speaker}) {}", seg.text);
+        }
     }
 
     // 결과 저장
+    let rendered = render_segments(&segments, output_format);
     let mut file = File::create(&srt_output)?;
-    file.write_all(srt_content.as_bytes())?;
+    file.write_all(rendered.as_bytes())?;
 
     println!("\n✨ 모든 작업이 완료되었습니다!");
     println!("📄 생성된 자막: {}", &srt_output);
 
-    // (옵션) 임시 WAV 파일 삭제를 원하시면 아래 주석을 해제하세요.
-    std::fs::remove_file(output_wav)?;
-
     Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_segments() -> Vec<Segment> {
+        vec![
+            Segment {
+                index: 1,
+                t0: 0,
+                t1: 150,
+                text: "hello world".to_string(),
+            },
+            Segment {
+                index: 2,
+                t0: 150,
+                t1: 300,
+                text: "second line".to_string(),
+            },
+        ]
+    }
+
+    #[test]
+    fn srt_time_formats_hours_minutes_seconds_millis() {
+        assert_eq!(format_srt_time(0), "00:00:00,000");
+        assert_eq!(format_srt_time(100), "00:00:01,000");
+        assert_eq!(format_srt_time(360_000), "01:00:00,000");
+    }
+
+    #[test]
+    fn vtt_time_uses_dot_separator() {
+        assert_eq!(format_vtt_time(100), "00:00:01.000");
+    }
+
+    #[test]
+    fn render_srt_includes_index_and_arrow() {
+        let out = render_srt(&sample_segments());
+        assert!(out.contains("1\n00:00:00,000 --> 00:00:01,500\nhello world\n\n"));
+        assert!(out.contains("2\n00:00:01,500 --> 00:00:03,000\nsecond line\n\n"));
+    }
+
+    #[test]
+    fn render_vtt_has_header_and_dot_times() {
+        let out = render_vtt(&sample_segments());
+        assert!(out.starts_with("WEBVTT\n\n"));
+        assert!(out.contains("00:00:00.000 --> 00:00:01.500"));
+    }
+
+    #[test]
+    fn render_json_escapes_control_characters() {
+        let segments = vec![Segment {
+            index: 1,
+            t0: 0,
+            t1: 100,
+            text: "line one\nline \"two\"\ttabbed".to_string(),
+        }];
+        let out = render_json(&segments);
+        assert!(out.contains("\\n"));
+        assert!(out.contains("\\\"two\\\""));
+        assert!(out.contains("\\t"));
+        // one segment -> exactly 3 lines: "[", the entry, "]" (no stray raw newlines from text)
+        assert_eq!(out.lines().count(), 3);
+    }
+
+    #[test]
+    fn render_delimited_strips_newlines_and_delimiter() {
+        let segments = vec![Segment {
+            index: 1,
+            t0: 0,
+            t1: 100,
+            text: "a,b\nc".to_string(),
+        }];
+        let csv = render_delimited(&segments, ',');
+        let data_line = csv.lines().nth(1).unwrap();
+        assert_eq!(data_line, "1,0,1000,a b c");
+    }
+
+    #[test]
+    fn wrap_tokens_splits_by_char_count_not_byte_length() {
+        // 일본어 문자는 UTF-8로 3바이트이므로, 바이트 기준으로 비교하면
+        // max_len=4(문자) 제한이 실제로는 한 글자만 지나도 줄바꿈된다.
+        let tokens = vec![
+            ("こ".to_string(), 0, 10),
+            ("ん".to_string(), 10, 20),
+            ("に".to_string(), 20, 30),
+            ("ち".to_string(), 30, 40),
+            ("は".to_string(), 40, 50),
+        ];
+        let lines = wrap_tokens_into_lines(&tokens, 4, false);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].2.chars().count(), 4);
+        assert_eq!(lines[1].2.chars().count(), 1);
+    }
+
+    #[test]
+    fn wrap_tokens_respects_word_boundaries() {
+        let tokens = vec![
+            ("foo".to_string(), 0, 10),
+            (" bar".to_string(), 10, 20),
+            (" baz".to_string(), 20, 30),
+        ];
+        let lines = wrap_tokens_into_lines(&tokens, 8, true);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].2, "foo bar");
+        assert_eq!(lines[1].2, "baz");
+    }
+
+    #[test]
+    fn wrap_tokens_falls_back_to_char_boundary_without_space_initial_tokens() {
+        // 일본어 토큰은 선행 공백이 없으므로, split_on_word=true라도 max_len에서
+        // 줄바꿈이 일어나야 한다 (공백 휴리스틱에 의해 무력화되어 한 줄로 뭉쳐지면 안 된다).
+        let tokens = vec![
+            ("こ".to_string(), 0, 10),
+            ("ん".to_string(), 10, 20),
+            ("に".to_string(), 20, 30),
+            ("ち".to_string(), 30, 40),
+            ("は".to_string(), 40, 50),
+        ];
+        let lines = wrap_tokens_into_lines(&tokens, 4, true);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].2.chars().count(), 4);
+        assert_eq!(lines[1].2.chars().count(), 1);
+    }
+
+    #[test]
+    fn resample_identity_when_rate_matches() {
+        let samples = vec![0.0, 0.5, 1.0, -1.0];
+        let out = resample_to_target(&samples, TARGET_SAMPLE_RATE);
+        assert_eq!(out, samples);
+    }
+
+    #[test]
+    fn resample_changes_length_with_rate() {
+        let samples = vec![0.0; 32000];
+        let out = resample_to_target(&samples, 32000);
+        assert_eq!(out.len(), 16000);
+    }
+
+    #[test]
+    fn channel_energy_is_zero_for_silence() {
+        let samples = vec![0.0; 1000];
+        assert_eq!(channel_energy(&samples, 0, 1000), 0.0);
+    }
+
+    #[test]
+    fn label_speaker_picks_louder_channel() {
+        let quiet = vec![0.01; 1600];
+        let loud = vec![0.9; 1600];
+        // t0=0, t1=10 -> 10*10ms*16samples/ms = 1600 samples
+        assert_eq!(label_speaker(&quiet, &loud, 0, 10), 1);
+        assert_eq!(label_speaker(&loud, &quiet, 0, 10), 0);
+    }
 }
\ No newline at end of file